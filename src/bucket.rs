@@ -4,8 +4,14 @@ use std::marker::PhantomData;
 
 use cosmwasm::errors::Result;
 use cosmwasm::traits::{ReadonlyStorage, Storage};
-
-use crate::namespace_helpers::{get_with_prefix, key_prefix, key_prefix_nested, set_with_prefix};
+#[cfg(feature = "iterator")]
+use cosmwasm::traits::Order;
+
+use crate::namespace_helpers::{
+    get_with_prefix, key_prefix, key_prefix_nested, remove_with_prefix, set_with_prefix,
+};
+#[cfg(feature = "iterator")]
+use crate::prefix::range_with_prefix;
 use crate::type_helpers::{may_deserialize, must_deserialize, serialize};
 
 pub fn bucket<'a, S: Storage, T>(namespace: &[u8], storage: &'a mut S) -> Bucket<'a, S, T>
@@ -74,6 +80,12 @@ where
         may_deserialize(&value)
     }
 
+    /// remove will delete the value at the given key, if any
+    pub fn remove(&mut self, key: &[u8]) -> Result<()> {
+        remove_with_prefix(self.storage, &self.prefix, key);
+        Ok(())
+    }
+
     /// update will load the data, perform the specified action, and store the result
     /// in the database. This is shorthand for some common sequences, which may be useful.
     /// Note that this only updates *pre-existing* values. If you want to modify possibly
@@ -89,7 +101,7 @@ where
 
     /// may_update is like update, but can handle missing values:
     /// * If there is no data at this key, the input is None
-    /// * We don't save data if the action returns None
+    /// * If the action returns None, the key is deleted (if it was present at all)
     ///
     /// This is the least stable of the APIs, and definitely needs some usage
     pub fn may_update(
@@ -99,11 +111,27 @@ where
     ) -> Result<Option<T>> {
         let input = self.may_load(key)?;
         let output = action(input)?;
-        if let Some(data) = &output {
-            self.save(key, data)?;
+        match &output {
+            Some(data) => self.save(key, data)?,
+            None => self.remove(key)?,
         }
         Ok(output)
     }
+
+    /// range allows iteration over a set of keys, either forwards or backwards.
+    /// The key is returned with the bucket's namespace stripped off, and the value
+    /// deserialized as for `load`.
+    #[cfg(feature = "iterator")]
+    pub fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Result<(Vec<u8>, T)>> + 'b> {
+        let mapped = range_with_prefix(&*self.storage, &self.prefix, start, end, order)
+            .map(|(k, v)| must_deserialize(&Some(v)).map(|v| (k, v)));
+        Box::new(mapped)
+    }
 }
 
 pub struct ReadonlyBucket<'a, S: ReadonlyStorage, T>
@@ -148,6 +176,21 @@ where
         let value = get_with_prefix(self.storage, &self.prefix, key);
         may_deserialize(&value)
     }
+
+    /// range allows iteration over a set of keys, either forwards or backwards.
+    /// The key is returned with the bucket's namespace stripped off, and the value
+    /// deserialized as for `load`.
+    #[cfg(feature = "iterator")]
+    pub fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = Result<(Vec<u8>, T)>> + 'b> {
+        let mapped = range_with_prefix(self.storage, &self.prefix, start, end, order)
+            .map(|(k, v)| must_deserialize(&Some(v)).map(|v| (k, v)));
+        Box::new(mapped)
+    }
 }
 
 #[cfg(test)]
@@ -334,8 +377,7 @@ mod test {
         assert_eq!(loaded.age, 42);
         assert_eq!(loaded.name.as_str(), "Maria");
 
-        // update with same function (don't change set values)
-        // only set first time
+        // returning None now deletes the existing entry (true upsert/delete semantics)
         let val = bucket
             .may_update(b"first", &|t| match t {
                 Some(_) => Ok(None),
@@ -347,9 +389,50 @@ mod test {
             .unwrap();
         assert!(val.is_none());
 
-        // ensure data was not modified
-        let loaded = bucket.load(b"first").unwrap();
-        assert_eq!(loaded.age, 42);
-        assert_eq!(loaded.name.as_str(), "Maria");
+        // data was removed
+        assert_eq!(bucket.may_load(b"first").unwrap(), None);
+    }
+
+    #[test]
+    fn remove_works() {
+        let mut store = MockStorage::new();
+        let mut bucket = bucket::<_, Data>(b"data", &mut store);
+
+        let data = Data {
+            name: "Maria".to_string(),
+            age: 42,
+        };
+        bucket.save(b"maria", &data).unwrap();
+
+        bucket.remove(b"maria").unwrap();
+        assert_eq!(bucket.may_load(b"maria").unwrap(), None);
+
+        // removing a key that was never set is a no-op
+        bucket.remove(b"never-set").unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn range_lists_all_entries() {
+        let mut store = MockStorage::new();
+        let mut bucket = bucket::<_, Data>(b"data", &mut store);
+
+        let maria = Data {
+            name: "Maria".to_string(),
+            age: 42,
+        };
+        let john = Data {
+            name: "John".to_string(),
+            age: 54,
+        };
+        bucket.save(b"maria", &maria).unwrap();
+        bucket.save(b"john", &john).unwrap();
+
+        let reader = bucket_read::<_, Data>(b"data", &store);
+        let all: Result<Vec<_>> = reader.range(None, None, Order::Ascending).collect();
+        assert_eq!(
+            all.unwrap(),
+            vec![(b"john".to_vec(), john), (b"maria".to_vec(), maria)]
+        );
     }
 }
@@ -1,17 +1,32 @@
-use serde::{Deserialize, Serialize};
+use named_type::NamedType;
+use serde::{de::DeserializeOwned, ser::Serialize};
+use std::collections::HashMap;
 
 use cosmwasm::errors::Result;
+#[cfg(feature = "iterator")]
+use cosmwasm::errors::ContractErr;
 use cosmwasm::traits::{ReadonlyStorage, Storage};
+#[cfg(feature = "iterator")]
+use cosmwasm::traits::Order;
 
-use crate::namespace_helpers::key_prefix;
-use crate::typed::{typed, typed_read};
+use crate::namespace_helpers::{
+    get_with_prefix, key_prefix, key_prefix_nested, remove_with_prefix, set_with_prefix,
+};
+use crate::prefix::length_prefix;
+#[cfg(feature = "iterator")]
+use crate::prefix::namespace_upper_bound;
+use crate::type_helpers::{may_deserialize, must_deserialize, serialize};
 
-pub fn index<T, F>(namespace: &[u8], action: F) -> Index<T>
-    where F: Fn(&T) -> Vec<u8> + 'static {
-    Index {
-        prefix: key_prefix(namespace),
-        action: Box::new(action),
-    }
+/// indexed_bucket builds an IndexedBucket with no indexes registered yet. Chain
+/// `add_index` calls on the result before saving any data.
+pub fn indexed_bucket<'a, S: Storage, T>(
+    namespace: &'static [u8],
+    storage: &'a mut S,
+) -> IndexedBucket<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    IndexedBucket::new(namespace, storage)
 }
 
 pub struct Index<T> {
@@ -20,87 +35,319 @@ pub struct Index<T> {
 }
 
 impl<T> Index<T> {
-    fn calc_key(&self, item: &T) -> Vec<u8> {
-        let calc = (self.action)(item);
+    /// value_key_prefix is the namespace under which every primary key matching `value`
+    /// is stored as a composite key: `self.prefix || length_prefix(value) || pk`. The
+    /// value is length-prefixed so a scan over this prefix can't pick up neighbouring
+    /// values that merely share a byte prefix with `value`.
+    fn value_key_prefix(&self, value: &[u8]) -> Vec<u8> {
         let mut k = self.prefix.clone();
-        k.extend_from_slice(&calc);
+        k.extend_from_slice(&length_prefix(value));
         k
     }
-}
 
-
-/// IndexEntry is persisted to disk and lists all primary keys that have a given index value
-#[derive(Serialize, Deserialize, PartialEq, Debug, Clone, Default)]
-struct IndexEntry {
-    // TODO: make this Vec<Base64> in 0.7.0
-    pub refs: Vec<Vec<u8>>,
+    fn calc_value_key_prefix(&self, item: &T) -> Vec<u8> {
+        self.value_key_prefix(&(self.action)(item))
+    }
 }
 
-/*
-This is getting expensive.
-Saving an item without index is 1 write
-Creating an item with 1 index is 2 read + 2 write (1 read to check old value, 1 read+write to add_key)
-Updating an item with 1 index is 3 read + 3 write (1 read to check old value, 1 read+write to add_key, 1 read+write to remove_key)
+/// new_index builds an Index sub-namespaced under the owning bucket's namespace plus
+/// `name`, so it can never collide with another index. The index keys sit inside the
+/// bucket's own namespace: a primary key that starts with the encoded index name shares
+/// key space with that index.
+fn new_index<T>(
+    bucket_namespace: &[u8],
+    name: &'static str,
+    action: impl Fn(&T) -> Vec<u8> + 'static,
+) -> Index<T> {
+    Index {
+        prefix: key_prefix_nested(&[bucket_namespace, name.as_bytes()]),
+        action: Box::new(action),
+    }
+}
 
-It *may* be possible to reduce the number of reads, but writes cannot change
-*/
+// Each index value maps to zero or more composite keys of the form
+// `index_namespace || length_prefix(index_value) || pk`, each holding an empty tombstone
+// value. This makes every update O(1): add_key/remove_key are a single set/remove, and
+// "all primary keys for an index value" is a prefix range scan rather than a
+// read-modify-write of a growing Vec<Vec<u8>>.
+//
+// Migration note: this replaces the earlier `IndexEntry { refs: Vec<Vec<u8>> }` encoding.
+// Data written under the old scheme is not readable here and must be re-indexed (e.g. by
+// replaying `save` for every item already stored).
 
-// must do a read for old data
-fn write_index<S: Storage, T>(storage: &mut S, idx: &Index<T>, pk: &[u8], old_val: Option<&T>, new_val: &T) -> Result<()> {
-    let old_idx = old_val.map(|o| idx.calc_key(o));
-    let new_idx = idx.calc_key(new_val);
+// one read to check the old value, then a single set/remove per index (no read-modify-write)
+fn write_index<S: Storage, T>(
+    storage: &mut S,
+    idx: &Index<T>,
+    pk: &[u8],
+    old_val: Option<&T>,
+    new_val: &T,
+) -> Result<()> {
+    let old_prefix = old_val.map(|o| idx.calc_value_key_prefix(o));
+    let new_prefix = idx.calc_value_key_prefix(new_val);
 
     // no change is a no-op
-    if let Some(o) = &old_idx {
+    if let Some(o) = &old_prefix {
         // if it unchanged, it is a no-op
-        if o == &new_idx {
+        if o == &new_prefix {
             return Ok(());
         }
         // otherwise, remove it
-        remove_key(storage, o.as_slice(), pk)?;
+        remove_key(storage, o, pk);
     }
 
     // now add the new pk
-    add_key(storage, new_idx.as_slice(), pk)
+    add_key(storage, &new_prefix, pk);
+    Ok(())
 }
 
-fn remove_key<S: Storage>(storage: &mut S, idx: &[u8], pk: &[u8]) -> Result<()> {
-    let mut db = typed(storage);
-    let mut entry: IndexEntry = db.load(idx)?;
-    // TODO: error if not found?
-    entry.refs = entry.refs.into_iter().filter(|r| r.as_slice() != pk).collect();
-    db.save(idx, &entry)
+fn add_key<S: Storage>(storage: &mut S, value_prefix: &[u8], pk: &[u8]) {
+    let mut k = value_prefix.to_vec();
+    k.extend_from_slice(pk);
+    storage.set(&k, b"");
 }
 
-fn add_key<S: Storage>(storage: &mut S, idx: &[u8], pk: &[u8]) -> Result<()> {
-    let mut db = typed(storage);
-    let mut entry: IndexEntry = db.may_load(idx)?.unwrap_or_default();
-    entry.refs.push(pk.to_vec());
-    db.save(idx, &entry)
+fn remove_key<S: Storage>(storage: &mut S, value_prefix: &[u8], pk: &[u8]) {
+    let mut k = value_prefix.to_vec();
+    k.extend_from_slice(pk);
+    storage.remove(&k);
 }
 
-fn load_keys<S: ReadonlyStorage>(storage: &S, idx: &[u8]) -> Result<Option<IndexEntry>> {
-    let db = typed_read(storage);
-    db.may_load(idx)
+/// IndexedBucket wraps a primary keyed collection and keeps a set of named secondary
+/// indexes in sync with it. Each index lives in its own sub-namespace (bucket namespace
+/// plus index name) so it can never collide with another index. That sub-namespace is
+/// nested inside the primary data's namespace, so primary keys are not kept apart from it.
+pub struct IndexedBucket<'a, S: Storage, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    storage: &'a mut S,
+    namespace: &'static [u8],
+    prefix: Vec<u8>,
+    indexes: HashMap<&'static str, Index<T>>,
+}
+
+impl<'a, S: Storage, T> IndexedBucket<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    pub fn new(namespace: &'static [u8], storage: &'a mut S) -> Self {
+        IndexedBucket {
+            prefix: key_prefix(namespace),
+            namespace,
+            storage,
+            indexes: HashMap::new(),
+        }
+    }
+
+    /// add_index registers a secondary index under `name`, computing its key from each
+    /// item with `idx_fn`. Call this right after construction, before saving any data.
+    pub fn add_index(
+        mut self,
+        name: &'static str,
+        idx_fn: impl Fn(&T) -> Vec<u8> + 'static,
+    ) -> Self {
+        self.indexes
+            .insert(name, new_index(self.namespace, name, idx_fn));
+        self
+    }
+
+    /// load will return an error if no data is set at the given primary key, or on parse error
+    pub fn load(&self, pk: &[u8]) -> Result<T> {
+        let value = get_with_prefix(self.storage, &self.prefix, pk);
+        must_deserialize(&value)
+    }
+
+    /// may_load will parse the data stored at the primary key if present, returns Ok(None)
+    /// if no data there. Returns an error on issues parsing
+    pub fn may_load(&self, pk: &[u8]) -> Result<Option<T>> {
+        let value = get_with_prefix(self.storage, &self.prefix, pk);
+        may_deserialize(&value)
+    }
+
+    /// save writes `data` at `pk` and updates every registered index to match, removing
+    /// any stale index entry that pointed at the previous value stored there (if any).
+    pub fn save(&mut self, pk: &[u8], data: &T) -> Result<()> {
+        let old_val = self.may_load(pk)?;
+        for idx in self.indexes.values() {
+            write_index(self.storage, idx, pk, old_val.as_ref(), data)?;
+        }
+        set_with_prefix(self.storage, &self.prefix, pk, &serialize(data)?);
+        Ok(())
+    }
+
+    /// remove deletes the item at `pk`, unwinding it from every registered index.
+    pub fn remove(&mut self, pk: &[u8]) -> Result<()> {
+        if let Some(old_val) = self.may_load(pk)? {
+            for idx in self.indexes.values() {
+                remove_key(self.storage, &idx.calc_value_key_prefix(&old_val), pk);
+            }
+        }
+        remove_with_prefix(self.storage, &self.prefix, pk);
+        Ok(())
+    }
+
+    /// items_by_index resolves every primary key stored under `index_value` for the index
+    /// registered as `name` back to its full item, via a range scan over the index's
+    /// composite keys.
+    #[cfg(feature = "iterator")]
+    pub fn items_by_index<'b>(
+        &'b self,
+        name: &str,
+        index_value: &[u8],
+    ) -> Result<Box<dyn Iterator<Item = Result<(Vec<u8>, T)>> + 'b>> {
+        let idx = match self.indexes.get(name) {
+            Some(idx) => idx,
+            None => {
+                return ContractErr {
+                    msg: "no such index registered",
+                }
+                .fail()
+            }
+        };
+        let prefix = idx.value_key_prefix(index_value);
+        let prefix_len = prefix.len();
+        let end = namespace_upper_bound(&prefix);
+        let iter = self
+            .storage
+            .range(Some(&prefix), Some(&end), Order::Ascending)
+            .map(move |(k, _)| k[prefix_len..].to_vec())
+            .map(move |pk| self.load(&pk).map(|item| (pk, item)));
+        Ok(Box::new(iter))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use cosmwasm::mock::MockStorage;
+    use named_type_derive::NamedType;
     use serde::{Deserialize, Serialize};
 
-    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[derive(Serialize, Deserialize, PartialEq, Debug, Clone, NamedType)]
     struct Person {
         pub name: String,
         pub age: u32,
     }
 
     #[test]
-    fn build_index() {
-        let idx = index(b"foo", |p: &Person| p.age.to_be_bytes().to_vec());
+    fn save_writes_composite_index_key() {
+        let mut store = MockStorage::new();
+        let mut bucket = indexed_bucket::<_, Person>(b"people", &mut store)
+            .add_index("age", |p: &Person| p.age.to_be_bytes().to_vec());
+
+        bucket
+            .save(
+                b"fred",
+                &Person {
+                    name: "Fred".to_string(),
+                    age: 127,
+                },
+            )
+            .unwrap();
+
+        // index_namespace || length_prefix(index_value) || pk. key_prefix_nested
+        // encodes each namespace segment with a 2-byte big-endian length, same as
+        // key_prefix (see the length of "people" and "age" below); the index value
+        // itself is length-prefixed with crate::prefix::length_prefix's 1-byte scheme.
+        let mut expected = vec![
+            0, 6, b'p', b'e', b'o', b'p', b'l', b'e', 0, 3, b'a', b'g', b'e',
+        ];
+        expected.extend_from_slice(&[4, 0, 0, 0, 127]);
+        expected.extend_from_slice(b"fred");
+
+        assert_eq!(store.get(&expected), Some(b"".to_vec()));
+    }
 
-        let expected = vec![0u8, 3, b'f', b'o', b'o', 0, 0, 0, 127];
-        let trial = idx.calc_key(&Person{ name: "Fred".to_string(), age: 127 });
-        assert_eq!(trial, expected);
+    #[test]
+    fn save_and_load_by_pk() {
+        let mut store = MockStorage::new();
+        let mut bucket = indexed_bucket::<_, Person>(b"people", &mut store)
+            .add_index("age", |p: &Person| p.age.to_be_bytes().to_vec());
+
+        let fred = Person {
+            name: "Fred".to_string(),
+            age: 42,
+        };
+        bucket.save(b"fred", &fred).unwrap();
+
+        let loaded = bucket.load(b"fred").unwrap();
+        assert_eq!(loaded, fred);
+        assert_eq!(bucket.may_load(b"never-set").unwrap(), None);
+    }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn query_by_index() {
+        let mut store = MockStorage::new();
+        let mut bucket = indexed_bucket::<_, Person>(b"people", &mut store)
+            .add_index("age", |p: &Person| p.age.to_be_bytes().to_vec());
+
+        let fred = Person {
+            name: "Fred".to_string(),
+            age: 42,
+        };
+        let mary = Person {
+            name: "Mary".to_string(),
+            age: 42,
+        };
+        bucket.save(b"fred", &fred).unwrap();
+        bucket.save(b"mary", &mary).unwrap();
+
+        let matches: Result<Vec<_>> = bucket
+            .items_by_index("age", &42u32.to_be_bytes())
+            .unwrap()
+            .collect();
+        assert_eq!(
+            matches.unwrap(),
+            vec![(b"fred".to_vec(), fred.clone()), (b"mary".to_vec(), mary)]
+        );
+
+        // updating the index value moves the entry to the new bucket
+        let mut older_fred = fred;
+        older_fred.age = 43;
+        bucket.save(b"fred", &older_fred).unwrap();
+
+        let matches: Vec<_> = bucket
+            .items_by_index("age", &42u32.to_be_bytes())
+            .unwrap()
+            .collect();
+        assert_eq!(matches.len(), 1);
+        let matches: Result<Vec<_>> = bucket
+            .items_by_index("age", &43u32.to_be_bytes())
+            .unwrap()
+            .collect();
+        assert_eq!(matches.unwrap(), vec![(b"fred".to_vec(), older_fred)]);
+    }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn remove_unwinds_index() {
+        let mut store = MockStorage::new();
+        let mut bucket = indexed_bucket::<_, Person>(b"people", &mut store)
+            .add_index("age", |p: &Person| p.age.to_be_bytes().to_vec());
+
+        let fred = Person {
+            name: "Fred".to_string(),
+            age: 42,
+        };
+        bucket.save(b"fred", &fred).unwrap();
+        bucket.remove(b"fred").unwrap();
+
+        assert_eq!(bucket.may_load(b"fred").unwrap(), None);
+        let matches: Vec<_> = bucket
+            .items_by_index("age", &42u32.to_be_bytes())
+            .unwrap()
+            .collect();
+        assert_eq!(matches.len(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn query_unknown_index_errs() {
+        let mut store = MockStorage::new();
+        let bucket = indexed_bucket::<_, Person>(b"people", &mut store)
+            .add_index("age", |p: &Person| p.age.to_be_bytes().to_vec());
+        assert!(bucket.items_by_index("name", b"Fred").is_err());
     }
 }
@@ -1,4 +1,6 @@
 use cosmwasm::traits::{ReadonlyStorage, Storage};
+#[cfg(feature = "iterator")]
+use cosmwasm::traits::{Order, KV};
 
 // prefixed_ro is a helper function for less verbose usage
 pub fn prefixed_ro<'a, T: ReadonlyStorage>(prefix: &[u8], storage: &'a T) -> ReadonlyPrefixedStorage<'a, T> {
@@ -31,6 +33,18 @@ impl<'a, T: ReadonlyStorage> ReadonlyPrefixedStorage<'a, T> {
             storage,
         }
     }
+
+    /// range allows iteration over a set of keys, either forwards or backwards
+    /// Returns the raw (unprefixed) key alongside the value, as stored by `set`.
+    #[cfg(feature = "iterator")]
+    pub fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = KV> + 'b> {
+        range_with_prefix(self.storage, &self.prefix, start, end, order)
+    }
 }
 
 impl<'a, T: ReadonlyStorage> ReadonlyStorage for ReadonlyPrefixedStorage<'a, T> {
@@ -62,6 +76,18 @@ impl<'a, T: Storage> PrefixedStorage<'a, T> {
             storage,
         }
     }
+
+    /// range allows iteration over a set of keys, either forwards or backwards
+    /// Returns the raw (unprefixed) key alongside the value, as stored by `set`.
+    #[cfg(feature = "iterator")]
+    pub fn range<'b>(
+        &'b self,
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        order: Order,
+    ) -> Box<dyn Iterator<Item = KV> + 'b> {
+        range_with_prefix(&*self.storage, &self.prefix, start, end, order)
+    }
 }
 
 impl<'a, T: Storage> ReadonlyStorage for PrefixedStorage<'a, T> {
@@ -78,10 +104,16 @@ impl<'a, T: Storage> Storage for PrefixedStorage<'a, T> {
         k.extend_from_slice(key);
         self.storage.set(&k, value)
     }
+
+    fn remove(&mut self, key: &[u8]) {
+        let mut k = self.prefix.clone();
+        k.extend_from_slice(key);
+        self.storage.remove(&k)
+    }
 }
 
 // prepend length and store this
-fn length_prefix(prefix: &[u8]) -> Vec<u8> {
+pub(crate) fn length_prefix(prefix: &[u8]) -> Vec<u8> {
     let mut v = Vec::with_capacity(prefix.len() + 1);
     if prefix.len() > 255 {
         panic!("only supports prefixes up to length 255")
@@ -91,6 +123,56 @@ fn length_prefix(prefix: &[u8]) -> Vec<u8> {
     v
 }
 
+/// range_with_prefix runs a range query on the underlying store, bounding `start`/`end`
+/// (if given) to the given namespace and stripping the namespace back off each returned
+/// key. Gated behind the `iterator` feature since not every `Storage` backend supports it.
+#[cfg(feature = "iterator")]
+pub(crate) fn range_with_prefix<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    namespace: &[u8],
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    order: Order,
+) -> Box<dyn Iterator<Item = KV> + 'a> {
+    let start = match start {
+        Some(s) => concat(namespace, s),
+        None => namespace.to_vec(),
+    };
+    let end = match end {
+        Some(e) => concat(namespace, e),
+        None => namespace_upper_bound(namespace),
+    };
+
+    let prefix_len = namespace.len();
+    let mapped = storage
+        .range(Some(&start), Some(&end), order)
+        .map(move |(k, v)| (k[prefix_len..].to_vec(), v));
+    Box::new(mapped)
+}
+
+#[cfg(feature = "iterator")]
+fn concat(namespace: &[u8], key: &[u8]) -> Vec<u8> {
+    let mut k = namespace.to_vec();
+    k.extend_from_slice(key);
+    k
+}
+
+/// namespace_upper_bound returns the smallest key that is strictly greater than all keys
+/// starting with `namespace`, i.e. an exclusive upper bound for a prefix range scan.
+/// A namespace made only of 0xff bytes has no such key; for it an empty vector is returned.
+#[cfg(feature = "iterator")]
+pub(crate) fn namespace_upper_bound(namespace: &[u8]) -> Vec<u8> {
+    let mut end = namespace.to_vec();
+    // trailing 0xff bytes cannot be incremented: drop them and increment the byte before
+    while end.last() == Some(&255) {
+        end.pop();
+    }
+    if let Some(last) = end.last_mut() {
+        *last += 1;
+    }
+    end
+}
+
 // prepend length and store this
 fn multi_length_prefix(prefixes: &[&[u8]]) -> Vec<u8> {
     let mut size = prefixes.len();
@@ -136,6 +218,16 @@ mod test {
         //        assert_eq!(Some(b"gotcha".to_vec()), foo.get(b"bar"));
     }
 
+    #[test]
+    fn remove_works() {
+        let mut storage = MockStorage::new();
+
+        let mut foo = PrefixedStorage::new(b"foo", &mut storage);
+        foo.set(b"bar", b"gotcha");
+        foo.remove(b"bar");
+        assert_eq!(None, foo.get(b"bar"));
+    }
+
     #[test]
     fn multi_level() {
         let mut storage = MockStorage::new();
@@ -177,4 +269,33 @@ mod test {
         let read_bar = prefixed_ro(b"bar", &store);
         assert_eq!(b"bar".to_vec(), read_bar.get(b"one").unwrap());
     }
+
+    #[test]
+    #[cfg(feature = "iterator")]
+    fn range_scans_prefix_only() {
+        let mut storage = MockStorage::new();
+
+        let mut foo = PrefixedStorage::new(b"foo", &mut storage);
+        foo.set(b"one", b"1");
+        foo.set(b"two", b"2");
+
+        let mut bar = PrefixedStorage::new(b"bar", &mut storage);
+        bar.set(b"three", b"3");
+
+        let rfoo = ReadonlyPrefixedStorage::new(b"foo", &storage);
+        let all: Vec<_> = rfoo.range(None, None, Order::Ascending).collect();
+        assert_eq!(
+            all,
+            vec![
+                (b"one".to_vec(), b"1".to_vec()),
+                (b"two".to_vec(), b"2".to_vec()),
+            ]
+        );
+
+        // bounded range only returns matches within start/end
+        let bounded: Vec<_> = rfoo
+            .range(Some(b"one"), Some(b"two"), Order::Ascending)
+            .collect();
+        assert_eq!(bounded, vec![(b"one".to_vec(), b"1".to_vec())]);
+    }
 }
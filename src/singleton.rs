@@ -0,0 +1,224 @@
+use named_type::NamedType;
+use serde::{de::DeserializeOwned, ser::Serialize};
+use std::marker::PhantomData;
+
+use cosmwasm::errors::Result;
+use cosmwasm::traits::{ReadonlyStorage, Storage};
+
+use crate::namespace_helpers::{get_with_prefix, key_prefix, set_with_prefix};
+use crate::type_helpers::{may_deserialize, must_deserialize, serialize};
+
+pub fn singleton<'a, S: Storage, T>(namespace: &[u8], storage: &'a mut S) -> Singleton<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    Singleton::new(namespace, storage)
+}
+
+pub fn singleton_read<'a, S: ReadonlyStorage, T>(
+    namespace: &[u8],
+    storage: &'a S,
+) -> ReadonlySingleton<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    ReadonlySingleton::new(namespace, storage)
+}
+
+/// Singleton stores exactly one typed value under a namespace, with no per-call key.
+/// The namespace itself (length-prefixed, as for Bucket) is the storage key, so callers
+/// don't need to invent a throwaway key for things like config or other global state.
+pub struct Singleton<'a, S: Storage, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    storage: &'a mut S,
+    // see https://doc.rust-lang.org/std/marker/struct.PhantomData.html#unused-type-parameters for why this is needed
+    data: PhantomData<&'a T>,
+    key: Vec<u8>,
+}
+
+impl<'a, S: Storage, T> Singleton<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    pub fn new(namespace: &[u8], storage: &'a mut S) -> Self {
+        Singleton {
+            key: key_prefix(namespace),
+            storage,
+            data: PhantomData,
+        }
+    }
+
+    /// save will serialize the model and store, returns an error on serialization issues
+    pub fn save(&mut self, data: &T) -> Result<()> {
+        set_with_prefix(self.storage, &self.key, b"", &serialize(data)?);
+        Ok(())
+    }
+
+    /// load will return an error if no data is set at this key, or on parse error
+    pub fn load(&self) -> Result<T> {
+        let value = get_with_prefix(self.storage, &self.key, b"");
+        must_deserialize(&value)
+    }
+
+    /// may_load will parse the data stored if present, returns Ok(None) if no data there.
+    /// returns an error on issues parsing
+    pub fn may_load(&self) -> Result<Option<T>> {
+        let value = get_with_prefix(self.storage, &self.key, b"");
+        may_deserialize(&value)
+    }
+
+    /// update will load the data, perform the specified action, and store the result
+    /// in the database. This is shorthand for some common sequences, which may be useful.
+    ///
+    /// This is the least stable of the APIs, and definitely needs some usage
+    pub fn update(&mut self, action: &dyn Fn(T) -> Result<T>) -> Result<T> {
+        let input = self.load()?;
+        let output = action(input)?;
+        self.save(&output)?;
+        Ok(output)
+    }
+}
+
+pub struct ReadonlySingleton<'a, S: ReadonlyStorage, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    storage: &'a S,
+    // see https://doc.rust-lang.org/std/marker/struct.PhantomData.html#unused-type-parameters for why this is needed
+    data: PhantomData<&'a T>,
+    key: Vec<u8>,
+}
+
+impl<'a, S: ReadonlyStorage, T> ReadonlySingleton<'a, S, T>
+where
+    T: Serialize + DeserializeOwned + NamedType,
+{
+    pub fn new(namespace: &[u8], storage: &'a S) -> Self {
+        ReadonlySingleton {
+            key: key_prefix(namespace),
+            storage,
+            data: PhantomData,
+        }
+    }
+
+    /// load will return an error if no data is set at this key, or on parse error
+    pub fn load(&self) -> Result<T> {
+        let value = get_with_prefix(self.storage, &self.key, b"");
+        must_deserialize(&value)
+    }
+
+    /// may_load will parse the data stored if present, returns Ok(None) if no data there.
+    /// returns an error on issues parsing
+    pub fn may_load(&self) -> Result<Option<T>> {
+        let value = get_with_prefix(self.storage, &self.key, b"");
+        may_deserialize(&value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm::errors::ContractErr;
+    use cosmwasm::mock::MockStorage;
+    use named_type_derive::NamedType;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, NamedType, PartialEq, Debug)]
+    struct Config {
+        pub owner: String,
+        pub max_tokens: i32,
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut store = MockStorage::new();
+        let mut single = singleton::<_, Config>(b"config", &mut store);
+
+        assert_eq!(single.may_load().unwrap(), None);
+
+        let cfg = Config {
+            owner: "admin".to_string(),
+            max_tokens: 1234,
+        };
+        single.save(&cfg).unwrap();
+
+        assert_eq!(single.load().unwrap(), cfg);
+    }
+
+    #[test]
+    fn readonly_works() {
+        let mut store = MockStorage::new();
+        let mut single = singleton::<_, Config>(b"config", &mut store);
+
+        let cfg = Config {
+            owner: "admin".to_string(),
+            max_tokens: 1234,
+        };
+        single.save(&cfg).unwrap();
+
+        let reader = singleton_read::<_, Config>(b"config", &store);
+        assert_eq!(reader.load().unwrap(), cfg);
+    }
+
+    #[test]
+    fn isolated_from_other_namespaces() {
+        let mut store = MockStorage::new();
+
+        let mut config = singleton::<_, Config>(b"config", &mut store);
+        config
+            .save(&Config {
+                owner: "admin".to_string(),
+                max_tokens: 1234,
+            })
+            .unwrap();
+
+        let other = singleton_read::<_, Config>(b"other", &store);
+        assert_eq!(other.may_load().unwrap(), None);
+    }
+
+    #[test]
+    fn update_success() {
+        let mut store = MockStorage::new();
+        let mut single = singleton::<_, Config>(b"config", &mut store);
+
+        single
+            .save(&Config {
+                owner: "admin".to_string(),
+                max_tokens: 1234,
+            })
+            .unwrap();
+
+        let output = single
+            .update(&|mut c| {
+                c.max_tokens += 1;
+                Ok(c)
+            })
+            .unwrap();
+        assert_eq!(output.max_tokens, 1235);
+        assert_eq!(single.load().unwrap().max_tokens, 1235);
+    }
+
+    #[test]
+    fn update_fails_on_error() {
+        let mut store = MockStorage::new();
+        let mut single = singleton::<_, Config>(b"config", &mut store);
+
+        let init = Config {
+            owner: "admin".to_string(),
+            max_tokens: 1234,
+        };
+        single.save(&init).unwrap();
+
+        let output = single.update(&|_c| {
+            ContractErr {
+                msg: "cuz i feel like it",
+            }
+            .fail()
+        });
+        assert!(output.is_err());
+
+        assert_eq!(single.load().unwrap(), init);
+    }
+}